@@ -4,7 +4,9 @@ mod agreement; // Agreement model and mechanisms.
 mod types;
 
 use soroban_kit::{oracle, oracle_subscriber, storage};
-use soroban_sdk::{contract, contractimpl, contractmeta, token, Address, Env};
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contractmeta, contracttype, token, Address, Env, Vec,
+};
 use types::{MarketData, MarketDataKey};
 
 use crate::{
@@ -17,10 +19,79 @@ contractmeta!(
     val = "NFT royalty smart contract for the Litemint marketplace"
 );
 
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum RoyaltyError {
+    LicensorNotTokenOwner = 1,
+    InvalidRecurPeriod = 2,
+    InvalidCompensationShares = 3,
+    LicenseAlreadyExists = 4,
+    AlreadyInitialized = 5,
+    InvalidCommissionRate = 6,
+}
+
 pub trait RoyaltyInterface {
     fn execute(env: Env, property: Address) -> License;
     fn pay(env: Env, property: Address, licensee: Address) -> License;
-    fn add_property(env: Env, terms: Terms);
+    fn add_property(env: Env, terms: Terms) -> Result<(), RoyaltyError>;
+}
+
+// Validates a `Terms` incrementally and reports the first violated
+// invariant as a typed error instead of panicking, so a client gets an
+// actionable code back from `add_property` rather than a trapped call.
+struct TermsBuilder<'a> {
+    env: &'a Env,
+    terms: Terms,
+}
+
+impl<'a> TermsBuilder<'a> {
+    fn new(env: &'a Env, terms: Terms) -> Self {
+        Self { env, terms }
+    }
+
+    fn validate_licensor_ownership(self) -> Result<Self, RoyaltyError> {
+        if token::Client::new(self.env, &self.terms.property).balance(&self.terms.licensor) != 1 {
+            return Err(RoyaltyError::LicensorNotTokenOwner);
+        }
+        Ok(self)
+    }
+
+    fn validate_recur_period(self) -> Result<Self, RoyaltyError> {
+        if !(self.terms.recur_period > self.terms.grace_period || self.terms.recur_period == 0) {
+            return Err(RoyaltyError::InvalidRecurPeriod);
+        }
+        Ok(self)
+    }
+
+    fn validate_compensation(self) -> Result<Self, RoyaltyError> {
+        if let Compensation::Split(_, _, payees) = &self.terms.compensation {
+            let mut total: u32 = 0;
+            for (_, share) in payees.iter() {
+                total = total
+                  .checked_add(share)
+                  .ok_or(RoyaltyError::InvalidCompensationShares)?;
+            }
+            if total != TOTAL_BASIS_POINTS {
+                return Err(RoyaltyError::InvalidCompensationShares);
+            }
+        }
+        Ok(self)
+    }
+
+    fn validate_not_duplicate(self) -> Result<Self, RoyaltyError> {
+        if storage::has::<DataKey, License>(
+            self.env,
+            &DataKey::License(self.terms.property.clone()),
+        ) {
+            return Err(RoyaltyError::LicenseAlreadyExists);
+        }
+        Ok(self)
+    }
+
+    fn build(self) -> Result<Terms, RoyaltyError> {
+        Ok(self.terms)
+    }
 }
 
 pub trait Subscriber {
@@ -40,11 +111,8 @@ impl oracle::Events<Address, MarketData> for RoyaltyContract {
 
     fn on_sync_receive(env: &Env, topic: &Address, envelope: &oracle::Envelope, data: &MarketData) {
         require_broker_whitelisted(env, &envelope.broker);
-        storage::set::<MarketDataKey, MarketData>(
-            &env,
-            &MarketDataKey::Index(topic.clone()),
-            reconcile_data(&mut data.clone()),
-        );
+        record_broker_feed(env, topic, &envelope.broker, data);
+        update_aggregated_index(env, topic);
     }
 
     fn on_async_receive(
@@ -55,16 +123,221 @@ impl oracle::Events<Address, MarketData> for RoyaltyContract {
     ) {
         require_broker_whitelisted(env, &envelope.broker);
         envelope.broker.require_auth();
-        storage::set::<MarketDataKey, MarketData>(
-            &env,
-            &MarketDataKey::Index(topic.clone()),
-            reconcile_data(&mut data.clone()),
-        );
+        record_broker_feed(env, topic, &envelope.broker, data);
+        update_aggregated_index(env, topic);
     }
 }
 
-fn reconcile_data<'a>(data: &'a mut MarketData) -> &'a mut MarketData {
-    data
+// How long a broker's submission stays eligible for the quorum/median
+// resolution before it is considered stale and excluded.
+const FRESHNESS_WINDOW: u64 = 300;
+
+#[contracttype]
+#[derive(Clone)]
+struct BrokerObservation {
+    timestamp: u64,
+    price: i128,
+    asset: Address,
+}
+
+// Caps how many distinct brokers a topic's resolver enumerates; once full,
+// the stalest-reporting broker is evicted to make way so a topic that
+// outlives many broker rotations doesn't grow this list without bound.
+const MAX_BROKERS_PER_TOPIC: u32 = 32;
+
+// Finds the broker with the oldest (or missing) observation, so eviction
+// drops whoever has actually gone quiet rather than whoever joined first.
+fn stalest_broker_index(env: &Env, topic: &Address, brokers: &Vec<Address>) -> u32 {
+    let mut stalest_index = 0;
+    let mut stalest_timestamp = u64::MAX;
+    for i in 0..brokers.len() {
+        let timestamp = storage::get::<MarketDataKey, BrokerObservation>(
+            env,
+            &MarketDataKey::BrokerFeed(topic.clone(), brokers.get_unchecked(i)),
+        )
+        .map(|observation| observation.timestamp)
+        .unwrap_or(0);
+        if timestamp < stalest_timestamp {
+            stalest_timestamp = timestamp;
+            stalest_index = i;
+        }
+    }
+    stalest_index
+}
+
+// Records `broker`'s latest reading for `topic`, tracking the set of brokers
+// that have ever reported so the resolver can enumerate them without relying
+// on host-side key iteration.
+fn record_broker_feed(env: &Env, topic: &Address, broker: &Address, data: &MarketData) {
+    let brokers_key = MarketDataKey::Brokers(topic.clone());
+    let mut brokers = storage::get_or_else::<MarketDataKey, Vec<Address>, _, _>(
+        env,
+        &brokers_key,
+        |opt| opt.unwrap_or(Vec::new(env)),
+    );
+    if !brokers.contains(broker) {
+        while brokers.len() >= MAX_BROKERS_PER_TOPIC {
+            let stale_index = stalest_broker_index(env, topic, &brokers);
+            let stale_broker = brokers.get_unchecked(stale_index);
+            storage::remove::<MarketDataKey>(
+                env,
+                &MarketDataKey::BrokerFeed(topic.clone(), stale_broker),
+            );
+            brokers.remove(stale_index);
+        }
+        brokers.push_back(broker.clone());
+        storage::set::<MarketDataKey, Vec<Address>>(env, &brokers_key, &brokers);
+    }
+
+    storage::set::<MarketDataKey, BrokerObservation>(
+        env,
+        &MarketDataKey::BrokerFeed(topic.clone(), broker.clone()),
+        &BrokerObservation {
+            timestamp: env.ledger().timestamp(),
+            price: data.price,
+            asset: data.asset.clone(),
+        },
+    );
+}
+
+// Resolves the fresh, quorum-backed median across every broker that has
+// reported for `topic`, and folds it through `reconcile_data`'s TWAP so
+// `MarketDataKey::Index` only ever reflects a manipulation-resistant price.
+// If quorum isn't met, the index is left untouched rather than falling back
+// to a single broker's feed.
+fn update_aggregated_index(env: &Env, topic: &Address) {
+    let min_quorum = storage::get::<AdminDataKey, AdminData>(env, &AdminDataKey::Root)
+      .map(|admin| admin.min_quorum)
+      .unwrap_or(1);
+
+    let brokers = storage::get_or_else::<MarketDataKey, Vec<Address>, _, _>(
+        env,
+        &MarketDataKey::Brokers(topic.clone()),
+        |opt| opt.unwrap_or(Vec::new(env)),
+    );
+
+    let now = env.ledger().timestamp();
+    let mut prices: Vec<i128> = Vec::new(env);
+    let mut latest_asset: Option<Address> = None;
+    let mut latest_timestamp = 0u64;
+    for broker in brokers.iter() {
+        if let Some(observation) = storage::get::<MarketDataKey, BrokerObservation>(
+            env,
+            &MarketDataKey::BrokerFeed(topic.clone(), broker),
+        ) {
+            if now - observation.timestamp <= FRESHNESS_WINDOW {
+                prices.push_back(observation.price);
+                if observation.timestamp >= latest_timestamp {
+                    latest_timestamp = observation.timestamp;
+                    latest_asset = Some(observation.asset.clone());
+                }
+            }
+        }
+    }
+
+    if prices.len() < min_quorum {
+        return;
+    }
+
+    let aggregated = MarketData {
+        price: median_price(&mut prices),
+        asset: latest_asset.unwrap(),
+    };
+
+    storage::set::<MarketDataKey, MarketData>(
+        env,
+        &MarketDataKey::Index(topic.clone()),
+        &reconcile_data(env, topic, &aggregated),
+    );
+}
+
+// Simple insertion sort: broker counts per topic are small, so this beats
+// pulling in a sorting dependency for `no_std`.
+fn median_price(prices: &mut Vec<i128>) -> i128 {
+    for i in 1..prices.len() {
+        let value = prices.get_unchecked(i);
+        let mut j = i;
+        while j > 0 && prices.get_unchecked(j - 1) > value {
+            prices.set(j, prices.get_unchecked(j - 1));
+            j -= 1;
+        }
+        prices.set(j, value);
+    }
+
+    let len = prices.len();
+    let mid = len / 2;
+    if len % 2 == 0 {
+        (prices.get_unchecked(mid - 1) + prices.get_unchecked(mid)) / 2
+    } else {
+        prices.get_unchecked(mid)
+    }
+}
+
+// Maximum number of price observations retained per topic; older entries
+// are evicted once the ring fills, independently of the TWAP window.
+const MAX_OBSERVATIONS: u32 = 20;
+
+#[contracttype]
+#[derive(Clone)]
+struct PriceObservation {
+    timestamp: u64,
+    price: i128,
+}
+
+// Folds a freshly-delivered `MarketData` into the rolling observation set for
+// `topic` and returns the time-weighted average price over the admin-configured
+// window, so no single oracle update can move royalty settlement on its own.
+fn reconcile_data(env: &Env, topic: &Address, data: &MarketData) -> MarketData {
+    let window = storage::get::<AdminDataKey, AdminData>(env, &AdminDataKey::Root)
+      .map(|admin| admin.twap_window)
+      .unwrap_or(0);
+
+    let observations_key = MarketDataKey::Observations(topic.clone());
+    let mut observations = storage::get_or_else::<MarketDataKey, Vec<PriceObservation>, _, _>(
+        env,
+        &observations_key,
+        |opt| opt.unwrap_or(Vec::new(env)),
+    );
+
+    let now = env.ledger().timestamp();
+    observations.push_back(PriceObservation {
+        timestamp: now,
+        price: data.price,
+    });
+
+    while observations.len() > 0 && now - observations.get_unchecked(0).timestamp > window {
+        observations.remove(0);
+    }
+    while observations.len() > MAX_OBSERVATIONS {
+        observations.remove(0);
+    }
+
+    storage::set::<MarketDataKey, Vec<PriceObservation>>(env, &observations_key, &observations);
+
+    let price = if observations.len() < 2 {
+        data.price
+    } else {
+        let mut cumulative: i128 = 0;
+        let mut elapsed: u64 = 0;
+        let mut prev = observations.get_unchecked(0);
+        for i in 1..observations.len() {
+            let next = observations.get_unchecked(i);
+            let dt = next.timestamp - prev.timestamp;
+            cumulative += prev.price * dt as i128;
+            elapsed += dt;
+            prev = next;
+        }
+        if elapsed == 0 {
+            data.price
+        } else {
+            cumulative / elapsed as i128
+        }
+    };
+
+    MarketData {
+        price,
+        asset: data.asset.clone(),
+    }
 }
 
 impl Subscriber for RoyaltyContract {
@@ -89,7 +362,10 @@ impl RoyaltyInterface for RoyaltyContract {
     fn execute(env: Env, property: Address) -> License {
         let mut license =
             storage::get::<DataKey, License>(&env, &DataKey::License(property.clone())).unwrap();
-        agreement!(license.terms.compensation).execute(&env, &mut license);
+        match &license.terms.compensation {
+            Compensation::Split(..) => update_split_license_status(&env, &mut license),
+            _ => agreement!(license.terms.compensation).execute(&env, &mut license),
+        }
         storage::set::<DataKey, License>(&env, &DataKey::License(property), &license);
         license
     }
@@ -108,26 +384,37 @@ impl RoyaltyInterface for RoyaltyContract {
             1
         );
 
-        agreement!(license.terms.compensation).pay(&env, &licensee, &mut license);
+        match &license.terms.compensation {
+            Compensation::Split(currency, amount, payees) => {
+                // Mirrors the non-split mechanisms' own due/already-paid guard:
+                // `execute` is what ages a license out of `Paid`, so `pay` only
+                // accepts a payment once that's happened, not on every call.
+                assert_ne!(license.status, LicenseStatus::Paid);
+                distribute_split_payment(&env, &licensee, currency, *amount, payees);
+                let now = env.ledger().timestamp();
+                license.status = LicenseStatus::Paid;
+                if license.terms.recur_period > 0 {
+                    license.recur_time = now + license.terms.recur_period;
+                    license.grace_time = license.recur_time + license.terms.grace_period;
+                }
+            }
+            _ => agreement!(license.terms.compensation).pay(&env, &licensee, &mut license),
+        }
         storage::set::<DataKey, License>(&env, &DataKey::License(property), &license);
         license
     }
 
-    fn add_property(env: Env, terms: Terms) {
+    fn add_property(env: Env, terms: Terms) -> Result<(), RoyaltyError> {
         terms.licensor.require_auth();
 
+        let terms = TermsBuilder::new(&env, terms)
+          .validate_licensor_ownership()?
+          .validate_recur_period()?
+          .validate_compensation()?
+          .validate_not_duplicate()?
+          .build()?;
 
         let property = terms.property.clone();
-        assert!(terms.recur_period > terms.grace_period || terms.recur_period == 0);
-        assert(!storage::has::<DataKey, License>(
-            &env,
-            &DataKey::License(property.clone())
-        ));
-        assert_eq!(
-            token::Client::new(&env, &property).balance(&terms.licensor),
-            1
-        );
-
         token::Client::new(&env, &terms.lien).transfer(
             &terms.licensor,
             &env.current_contract_address(),
@@ -152,33 +439,90 @@ impl RoyaltyInterface for RoyaltyContract {
             false,
         );
         storage::set::<DataKey, License>(&env, &DataKey::License(property), &license);
+        Ok(())
     }
 }
 
 impl RoyaltyContract {
-    pub fn initialize(env: Env, admin: Address, commission_rate: i128) {
-        assert(!storage::has::<AdminDataKey, AdminData>(
-            &env,
-            &AdminDataKey::Root
-        ));
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        commission_rate: i128,
+        twap_window: u64,
+        min_quorum: u32,
+    ) -> Result<(), RoyaltyError> {
+        if storage::has::<AdminDataKey, AdminData>(&env, &AdminDataKey::Root) {
+            return Err(RoyaltyError::AlreadyInitialized);
+        }
+        if !(0..=100).contains(&commission_rate) {
+            return Err(RoyaltyError::InvalidCommissionRate);
+        }
         storage::set::<AdminDataKey, AdminData>(
             &env,
             &AdminDataKey::Root,
             &AdminData {
                 admin,
                 commission_rate,
+                twap_window,
+                min_quorum: min_quorum.max(1),
             },
         );
+        Ok(())
     }
 
     #[cfg(test)]
-    pub fn test_oracle_feed(env: Env, topic: Address, price: i128, asset: Address) {
-        storage::set::<MarketDataKey, MarketData>(
-            &env,
-            &MarketDataKey::Index(topic.clone()),
-            reconcile_data(&mut MarketData { price, asset }),
-        );
+    pub fn test_oracle_feed(env: Env, topic: Address, broker: Address, price: i128, asset: Address) {
+        let data = MarketData { price, asset };
+        record_broker_feed(&env, &topic, &broker, &data);
+        update_aggregated_index(&env, &topic);
+    }
+}
+
+// Total basis-point shares for a compensation mechanism must add up to
+// exactly 10_000 so a split payout is fully and only conserved across
+// its payees; other compensation mechanisms have nothing to check here.
+const TOTAL_BASIS_POINTS: u32 = 10_000;
+
+// Ages a `Split` license out of `Paid` once its recurring period comes due,
+// and further into `Delinquent` once a further grace window lapses too,
+// mirroring the transition the other compensation mechanisms drive from
+// `execute` so `pay` has a real due/already-paid signal to gate on. The
+// delinquency bound is computed from `recur_time`, not read off the stored
+// `grace_time`, so this stays correct even on the license's first cycle.
+fn update_split_license_status(env: &Env, license: &mut License) {
+    if (license.status == LicenseStatus::Paid || license.status == LicenseStatus::Due)
+        && license.terms.recur_period > 0
+    {
+        let now = env.ledger().timestamp();
+        let delinquent_time = license.recur_time + license.terms.grace_period;
+        if now >= delinquent_time {
+            license.status = LicenseStatus::Delinquent;
+        } else if now >= license.recur_time {
+            license.status = LicenseStatus::Due;
+        }
+    }
+}
+
+// Pays each payee its basis-point cut of `amount` in one transfer apiece,
+// folding the rounding remainder into the first payee so the sum of
+// transfers always equals `amount` exactly.
+fn distribute_split_payment(
+    env: &Env,
+    payer: &Address,
+    token: &Address,
+    amount: i128,
+    payees: &Vec<(Address, u32)>,
+) {
+    let token_client = token::Client::new(env, token);
+    let mut remainder = amount;
+    for i in 1..payees.len() {
+        let (payee, share) = payees.get_unchecked(i);
+        let cut = amount * share as i128 / TOTAL_BASIS_POINTS as i128;
+        remainder -= cut;
+        token_client.transfer(payer, &payee, &cut);
     }
+    let (first_payee, _) = payees.get_unchecked(0);
+    token_client.transfer(payer, &first_payee, &remainder);
 }
 
 fn update_broker_whitelist(env: &Env, broker: &Address, remove: bool) {
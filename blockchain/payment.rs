@@ -7,7 +7,9 @@ use soroban_kit::{
     fsm::{self, StateMachine},
     storage,
 };
-use soroban_sdk::{contract, contractimpl, contractmeta, vec, Address, BytesN, Env, Vec};
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contractmeta, token, vec, Address, BytesN, Env, Vec,
+};
 
 use crate::auctions::{behavior::BaseAuction, behavior::Dispatcher};
 use types::{AdminData, AuctionData, AuctionPhase, AuctionRegion, AuctionSettings, DataKey};
@@ -17,27 +19,123 @@ contractmeta!(
     val = "Auction smart contract for the Litemint marketplace"
 );
 
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum AuctionError {
+    NotInitialized = 1,
+    SellerNotTokenOwner = 2,
+    InconsistentBuyNowSettings = 3,
+    AlreadyInitialized = 4,
+    InvalidCommissionRate = 5,
+}
+
 pub trait AuctionContractTrait {
     fn upgrade(e: Env, wasm_hash: BytesN<32>);
     fn get_auction(env: Env, auction_id: u64) -> Option<AuctionData>;
     fn resolve(env: Env, auction_id: u64);
     fn place_sealed_bid(env: Env, auction_id: u64, buyer: Address, sealed_amount: BytesN<32>);
     fn place_bid(env: Env, auction_id: u64, buyer: Address, amount: i128, salt: Option<BytesN<32>>);
+    fn buy_now(env: Env, auction_id: u64, buyer: Address);
     fn extend(env: Env, auction_id: u64, duration: u64) -> bool;
-    fn start(env: Env, auction_settings: AuctionSettings) -> u64;
+    fn start(env: Env, auction_settings: AuctionSettings) -> Result<u64, AuctionError>;
     fn initialize(
         env: Env,
         admin: Address,
         anti_snipe_time: u64,
         commission_rate: i128,
         extendable_auctions: bool,
-    );
+    ) -> Result<(), AuctionError>;
     fn version(env: Env) -> Vec<u32>;
 }
 
+// Validates an `AuctionSettings` incrementally and reports the first
+// violated invariant as a typed error instead of panicking, so a client
+// gets an actionable code back from `start` rather than a trapped call.
+struct AuctionSettingsBuilder<'a> {
+    env: &'a Env,
+    settings: AuctionSettings,
+}
+
+impl<'a> AuctionSettingsBuilder<'a> {
+    fn new(env: &'a Env, settings: AuctionSettings) -> Self {
+        Self { env, settings }
+    }
+
+    fn validate_seller_ownership(self) -> Result<Self, AuctionError> {
+        if token::Client::new(self.env, &self.settings.token).balance(&self.settings.seller) != 1 {
+            return Err(AuctionError::SellerNotTokenOwner);
+        }
+        Ok(self)
+    }
+
+    // A buy-now price only makes sense once bidders can actually see it;
+    // a sealed phase that outlasts the whole auction would leave no window
+    // for `buy_now` to ever be callable.
+    fn validate_buy_now_consistency(self) -> Result<Self, AuctionError> {
+        if self.settings.buy_now_price > 0
+            && self.settings.sealed_phase_time > 0
+            && self.settings.sealed_phase_time >= self.settings.duration
+        {
+            return Err(AuctionError::InconsistentBuyNowSettings);
+        }
+        Ok(self)
+    }
+
+    fn build(self) -> Result<AuctionSettings, AuctionError> {
+        Ok(self.settings)
+    }
+}
+
 #[contract]
 struct AuctionContract;
 
+#[cfg(test)]
+fn has_sealed_phase_expired(_env: &Env, _auction_data: &AuctionData) -> bool {
+    true
+}
+
+#[cfg(not(test))]
+fn has_sealed_phase_expired(env: &Env, auction_data: &AuctionData) -> bool {
+    auction_data.start_time + auction_data.settings.sealed_phase_time <= env.ledger().timestamp()
+}
+
+// Automatic anti-snipe extensions allowed per auction before its scheduled
+// end can no longer be pushed back by a late bid.
+const MAX_AUTO_EXTENSIONS: u32 = 10;
+
+// Pushes the auction's scheduled end out by `anti_snipe_time` when a bid
+// lands within that window of the end, so a sniper can't win by bidding at
+// the buzzer. Only active when `extendable_auctions` is set, and capped at
+// `MAX_AUTO_EXTENSIONS` per auction so late bids cannot extend it forever.
+fn apply_anti_snipe_extension(env: &Env, auction_id: u64) {
+    let admin =
+        storage::get_or_else::<DataKey, AdminData, _, _>(env, &DataKey::AdminData, |opt| {
+            opt.unwrap()
+        });
+    if !admin.extendable_auctions || admin.anti_snipe_time == 0 {
+        return;
+    }
+
+    let mut auction_data =
+        storage::get::<DataKey, AuctionData>(env, &DataKey::AuctionData(auction_id)).unwrap();
+    if auction_data.auto_extensions >= MAX_AUTO_EXTENSIONS {
+        return;
+    }
+
+    let scheduled_end = auction_data.start_time + auction_data.settings.duration;
+    let now = env.ledger().timestamp();
+    if now <= scheduled_end && scheduled_end - now <= admin.anti_snipe_time {
+        auction_data.settings.duration += admin.anti_snipe_time;
+        auction_data.auto_extensions += 1;
+        storage::set::<DataKey, AuctionData>(
+            env,
+            &DataKey::AuctionData(auction_id),
+            &auction_data,
+        );
+    }
+}
+
 #[contractimpl]
 impl AuctionContractTrait for AuctionContract {
     fn get_auction(env: Env, auction_id: u64) -> Option<AuctionData> {
@@ -75,15 +173,6 @@ impl AuctionContractTrait for AuctionContract {
                 && auction_data.settings.discount_frequency > 0
         );
 
-        #[cfg(test)]
-        let has_sealed_phase_expired = |_env: &Env, _auction_data: &AuctionData| -> bool { true };
-
-        #[cfg(not(test))]
-        let has_sealed_phase_expired = |env: &Env, auction_data: &AuctionData| -> bool {
-            auction_data.start_time + auction_data.settings.sealed_phase_time
-                <= env.ledger().timestamp()
-        };
-
         if dispatcher.is_sealed_bid_auction(&auction_data) {
             let region = AuctionRegion::Dispatcher(auction_id);
             if has_sealed_phase_expired(&env, &auction_data) {
@@ -95,7 +184,32 @@ impl AuctionContractTrait for AuctionContract {
             }
         }
 
-        dispatcher.place_bid(&env, auction_id, &buyer, amount, &salt);
+        // Only a bid that actually takes the lead can push the clock out;
+        // a non-leading revealed sealed bid must not buy more time.
+        let is_leading_bid = dispatcher.place_bid(&env, auction_id, &buyer, amount, &salt);
+        if is_leading_bid {
+            apply_anti_snipe_extension(&env, auction_id);
+        }
+    }
+
+    fn buy_now(env: Env, auction_id: u64, buyer: Address) {
+        buyer.require_auth();
+
+        let auction_data =
+            storage::get::<DataKey, AuctionData>(&env, &DataKey::AuctionData(auction_id)).unwrap();
+        assert!(auction_data.settings.buy_now_price > 0);
+
+        let dispatcher = dispatcher!(
+            auction_data.settings.discount_percent > 0
+                && auction_data.settings.discount_frequency > 0
+        );
+
+        assert!(
+            !dispatcher.is_sealed_bid_auction(&auction_data)
+                || has_sealed_phase_expired(&env, &auction_data)
+        );
+
+        dispatcher.buy_now(&env, auction_id, &buyer);
     }
 
     fn place_sealed_bid(env: Env, auction_id: u64, buyer: Address, sealed_amount: BytesN<32>) {
@@ -133,14 +247,18 @@ impl AuctionContractTrait for AuctionContract {
         }
     }
 
-    fn start(env: Env, auction_settings: AuctionSettings) -> u64 {
-        assert!(storage::has::<DataKey, AdminData>(
-            &env,
-            &DataKey::AdminData
-        ));
+    fn start(env: Env, auction_settings: AuctionSettings) -> Result<u64, AuctionError> {
+        if !storage::has::<DataKey, AdminData>(&env, &DataKey::AdminData) {
+            return Err(AuctionError::NotInitialized);
+        }
 
         auction_settings.seller.require_auth();
 
+        let auction_settings = AuctionSettingsBuilder::new(&env, auction_settings)
+            .validate_seller_ownership()?
+            .validate_buy_now_consistency()?
+            .build()?;
+
         let mut id = 0u64;
         env.prng().fill(&mut id);
         let auction_data = AuctionData::new(
@@ -155,7 +273,7 @@ impl AuctionContractTrait for AuctionContract {
                 && auction_data.settings.discount_frequency > 0
         )
       .start(&env, id, &auction_data);
-        id
+        Ok(id)
     }
 
     fn initialize(
@@ -164,11 +282,13 @@ impl AuctionContractTrait for AuctionContract {
         anti_snipe_time: u64,
         commission_rate: i128,
         extendable_auctions: bool,
-    ) {
-        assert(!storage::has::<DataKey, AdminData>(
-            &env,
-            &DataKey::AdminData
-        ));
+    ) -> Result<(), AuctionError> {
+        if storage::has::<DataKey, AdminData>(&env, &DataKey::AdminData) {
+            return Err(AuctionError::AlreadyInitialized);
+        }
+        if !(0..=100).contains(&commission_rate) {
+            return Err(AuctionError::InvalidCommissionRate);
+        }
 
         storage::set::<DataKey, AdminData>(
             &env,
@@ -176,10 +296,11 @@ impl AuctionContractTrait for AuctionContract {
             &AdminData {
                 admin,
                 anti_snipe_time: anti_snipe_time.min(60),
-                commission_rate: commission_rate.max(0).min(100),
+                commission_rate,
                 extendable_auctions,
             },
         );
+        Ok(())
     }
 
     fn upgrade(env: Env, wasm_hash: BytesN<32>) {